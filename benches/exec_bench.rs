@@ -0,0 +1,56 @@
+//! Compares the recursive `vm::exec_body` executor against the flat
+//! bytecode `bytecode::exec_flat` executor on the same compiled program.
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use nozomi::bytecode;
+use nozomi::io::{EofBehavior, Io};
+use nozomi::memory::{Memory, TapeConfig};
+use nozomi::vm;
+
+struct NullIo;
+
+impl Io for NullIo {
+    fn read_byte(&mut self) -> Option<u8> {
+        Some(0)
+    }
+    fn write_byte(&mut self, _byte: u8) {}
+}
+
+fn compile_mandelbrot() -> Vec<vm::InstWithOffset<u8>> {
+    // A tight, deeply-looping multiplication program, representative of
+    // hot inner loops in typical Brainfuck workloads.
+    let code = "++++++++[>++++++++<-]>[<++++>-]<[>+>+<<-]>>[<<+>>-]<<.";
+    let raw_insts = vm::parse::<u8>(code);
+    let insts = vm::extract_loops(&raw_insts).unwrap();
+    let insts = vm::optimize_basic(&insts);
+    let insts = vm::annotate_offset(&insts);
+    let insts = vm::delay_move_ptr(&insts);
+    let insts = vm::remove_zero_move_ptr(&insts);
+    vm::loop_to_addmul(&insts)
+}
+
+fn bench_recursive(c: &mut Criterion) {
+    let insts = compile_mandelbrot();
+    c.bench_function("exec_body (recursive)", |b| {
+        b.iter(|| {
+            let mut memory: Memory<u8> = Memory::new(TapeConfig::default());
+            let mut io = NullIo;
+            let _ = vm::exec(&insts, &mut memory, &mut io, EofBehavior::KeepCell);
+        })
+    });
+}
+
+fn bench_flat(c: &mut Criterion) {
+    let insts = compile_mandelbrot();
+    let code = bytecode::compile(&insts);
+    c.bench_function("exec_flat (iterative)", |b| {
+        b.iter(|| {
+            let mut memory: Memory<u8> = Memory::new(TapeConfig::default());
+            let mut io = NullIo;
+            let _ = bytecode::exec_flat(&code, &mut memory, &mut io, EofBehavior::KeepCell);
+        })
+    });
+}
+
+criterion_group!(benches, bench_recursive, bench_flat);
+criterion_main!(benches);
@@ -0,0 +1,160 @@
+//! Lowers the nested [`InstWithOffset`] tree into a linear instruction
+//! stream addressed by an instruction pointer, so execution is a single
+//! `while` loop instead of a recursive walk over the loop tree.
+
+use alloc::vec::Vec;
+
+use crate::error::NozomiError;
+use crate::io::{EofBehavior, Io};
+use crate::memory::{Cell, Memory};
+use crate::vm::InstWithOffset;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum FlatInst<T> {
+    AddI(isize, T),
+    MovePtr(isize),
+    Init(isize, T),
+    AddMul(isize, isize, T),
+    GetChar(isize),
+    PutChar(isize),
+    /// Jump to `target` if the current cell is zero.
+    JumpIfZero(usize),
+    /// Jump to `target` if the current cell is non-zero.
+    JumpIfNonZero(usize),
+}
+
+/// Lowers a loop-nested `InstWithOffset` tree into flat bytecode.
+///
+/// Emits with a two-pass patching scheme: a stack of pending loop-start
+/// indices is maintained; on loop entry a `JumpIfZero` placeholder is
+/// pushed, and on loop end a `JumpIfNonZero` back to the loop start is
+/// emitted and the matching `JumpIfZero` is patched to jump just past it.
+pub fn compile<T: Cell>(insts: &[InstWithOffset<T>]) -> Vec<FlatInst<T>> {
+    let mut code = Vec::new();
+    compile_into(insts, &mut code);
+    code
+}
+
+fn compile_into<T: Cell>(insts: &[InstWithOffset<T>], code: &mut Vec<FlatInst<T>>) {
+    for inst in insts {
+        match inst {
+            InstWithOffset::AddI(ofs, x) => code.push(FlatInst::AddI(*ofs, *x)),
+            InstWithOffset::MovePtr(x) => code.push(FlatInst::MovePtr(*x)),
+            InstWithOffset::Init(ofs, x) => code.push(FlatInst::Init(*ofs, *x)),
+            InstWithOffset::AddMul(ofs1, ofs2, x) => code.push(FlatInst::AddMul(*ofs1, *ofs2, *x)),
+            InstWithOffset::GetChar(ofs) => code.push(FlatInst::GetChar(*ofs)),
+            InstWithOffset::PutChar(ofs) => code.push(FlatInst::PutChar(*ofs)),
+            InstWithOffset::Loop(body) => {
+                let start = code.len();
+                code.push(FlatInst::JumpIfZero(0));
+                compile_into(body, code);
+                code.push(FlatInst::JumpIfNonZero(start + 1));
+                let end = code.len();
+                code[start] = FlatInst::JumpIfZero(end);
+            }
+        }
+    }
+}
+
+/// Executes flat bytecode with a non-recursive instruction-pointer loop.
+pub fn exec_flat<T: Cell>(
+    code: &[FlatInst<T>],
+    memory: &mut Memory<T>,
+    io: &mut impl Io,
+    eof: EofBehavior,
+) -> Result<usize, NozomiError> {
+    let mut ptr = 0isize;
+    let mut ip = 0usize;
+    let mut cycle_count = 0usize;
+    while ip < code.len() {
+        cycle_count += 1;
+        let oob = || NozomiError::PointerOutOfBounds { ip };
+        match &code[ip] {
+            FlatInst::AddI(ofs, x) => {
+                let index = ptr.checked_add(*ofs).ok_or_else(oob)?;
+                let value = memory.read(index).map_err(|_| oob())?;
+                memory.write(index, value.wrapping_add(*x)).map_err(|_| oob())?;
+                ip += 1;
+            }
+            FlatInst::MovePtr(x) => {
+                ptr = ptr.checked_add(*x).ok_or_else(oob)?;
+                ip += 1;
+            }
+            FlatInst::Init(ofs, x) => {
+                let index = ptr.checked_add(*ofs).ok_or_else(oob)?;
+                memory.write(index, *x).map_err(|_| oob())?;
+                ip += 1;
+            }
+            FlatInst::AddMul(ofs1, ofs2, x) => {
+                let index1 = ptr.checked_add(*ofs1).ok_or_else(oob)?;
+                let index2 = ptr.checked_add(*ofs2).ok_or_else(oob)?;
+                let src = memory.read(index1).map_err(|_| oob())?;
+                let dst = memory.read(index2).map_err(|_| oob())?;
+                memory
+                    .write(index2, dst.wrapping_add(src.wrapping_mul(*x)))
+                    .map_err(|_| oob())?;
+                ip += 1;
+            }
+            FlatInst::GetChar(ofs) => {
+                let index = ptr.checked_add(*ofs).ok_or_else(oob)?;
+                match io.read_byte() {
+                    Some(b) => memory.write(index, T::from_byte(b)),
+                    None => match eof {
+                        EofBehavior::KeepCell => Ok(()),
+                        EofBehavior::WriteZero => memory.write(index, T::default()),
+                        EofBehavior::WriteMax => memory.write(index, T::NEG_ONE),
+                    },
+                }
+                .map_err(|_| oob())?;
+                ip += 1;
+            }
+            FlatInst::PutChar(ofs) => {
+                let index = ptr.checked_add(*ofs).ok_or_else(oob)?;
+                let value = memory.read(index).map_err(|_| oob())?;
+                io.write_byte(value.to_byte());
+                ip += 1;
+            }
+            FlatInst::JumpIfZero(target) => {
+                if memory.read(ptr).map_err(|_| oob())? == T::default() {
+                    ip = *target;
+                } else {
+                    ip += 1;
+                }
+            }
+            FlatInst::JumpIfNonZero(target) => {
+                if memory.read(ptr).map_err(|_| oob())? != T::default() {
+                    ip = *target;
+                } else {
+                    ip += 1;
+                }
+            }
+        }
+    }
+    Ok(cycle_count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compile_flattens_loop() {
+        let insts = alloc::vec![
+            InstWithOffset::AddI(0, 3u8),
+            InstWithOffset::Loop(alloc::vec![
+                InstWithOffset::AddI(0, 255u8),
+                InstWithOffset::AddI(1, 1u8),
+            ]),
+        ];
+        assert_eq!(
+            compile(&insts),
+            alloc::vec![
+                FlatInst::AddI(0, 3u8),
+                FlatInst::JumpIfZero(5),
+                FlatInst::AddI(0, 255u8),
+                FlatInst::AddI(1, 1u8),
+                FlatInst::JumpIfNonZero(2),
+            ]
+        );
+    }
+}
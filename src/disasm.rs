@@ -0,0 +1,173 @@
+//! Human-readable dumps of the IR at each optimization stage, for
+//! diagnosing which loops were turned into `AddMul`/`Init` and why.
+
+use alloc::format;
+use alloc::string::String;
+#[cfg(feature = "std")]
+use core::fmt;
+
+use crate::memory::Cell;
+use crate::vm::{Inst, InstWithOffset};
+
+/// Which pass's output to print with `--dump-stage=<stage>`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Stage {
+    Parse,
+    Basic,
+    Offset,
+    Delay,
+    Addmul,
+}
+
+impl Stage {
+    pub fn from_name(name: &str) -> Option<Stage> {
+        match name {
+            "parse" => Some(Stage::Parse),
+            "basic" => Some(Stage::Basic),
+            "offset" => Some(Stage::Offset),
+            "delay" => Some(Stage::Delay),
+            "addmul" => Some(Stage::Addmul),
+            _ => None,
+        }
+    }
+}
+
+const INDENT: &str = "  ";
+
+fn write_indent(out: &mut String, depth: usize) {
+    for _ in 0..depth {
+        out.push_str(INDENT);
+    }
+}
+
+fn fmt_inst<T: Cell>(inst: &Inst<T>, depth: usize, out: &mut String) {
+    write_indent(out, depth);
+    match inst {
+        Inst::AddI(x) => out.push_str(&format!("AddI {}\n", x.as_signed())),
+        Inst::MovePtr(x) => out.push_str(&format!("MovePtr {}\n", x)),
+        Inst::Init(x) => out.push_str(&format!("Init @+0 = {}\n", x)),
+        Inst::GetChar => out.push_str("GetChar\n"),
+        Inst::PutChar => out.push_str("PutChar\n"),
+        Inst::Loop(body) => {
+            out.push_str("Loop [\n");
+            for inst in body {
+                fmt_inst(inst, depth + 1, out);
+            }
+            write_indent(out, depth);
+            out.push_str("]\n");
+        }
+    }
+}
+
+fn fmt_inst_with_offset<T: Cell>(inst: &InstWithOffset<T>, depth: usize, out: &mut String) {
+    write_indent(out, depth);
+    match inst {
+        InstWithOffset::AddI(ofs, x) => out.push_str(&format!("AddI @{:+} = {:+}\n", ofs, x.as_signed())),
+        InstWithOffset::MovePtr(x) => out.push_str(&format!("MovePtr {}\n", x)),
+        InstWithOffset::Init(ofs, x) => out.push_str(&format!("Init @{:+} = {}\n", ofs, x)),
+        InstWithOffset::AddMul(src, dst, x) => {
+            out.push_str(&format!("AddMul src={:+} dst={:+} *{}\n", src, dst, x))
+        }
+        InstWithOffset::GetChar(ofs) => out.push_str(&format!("GetChar @{:+}\n", ofs)),
+        InstWithOffset::PutChar(ofs) => out.push_str(&format!("PutChar @{:+}\n", ofs)),
+        InstWithOffset::Loop(body) => {
+            out.push_str("Loop [\n");
+            for inst in body {
+                fmt_inst_with_offset(inst, depth + 1, out);
+            }
+            write_indent(out, depth);
+            out.push_str("]\n");
+        }
+    }
+}
+
+/// Renders an indented, loop-nested listing of `Inst`s.
+pub fn disasm_inst<T: Cell>(insts: &[Inst<T>]) -> String {
+    let mut out = String::new();
+    for inst in insts {
+        fmt_inst(inst, 0, &mut out);
+    }
+    out
+}
+
+/// Renders an indented, loop-nested listing of `InstWithOffset`s, with
+/// explicit offsets (e.g. `AddMul src=+0 dst=+1 *2`, `Init @+0 = 0`).
+pub fn disasm_inst_with_offset<T: Cell>(insts: &[InstWithOffset<T>]) -> String {
+    let mut out = String::new();
+    for inst in insts {
+        fmt_inst_with_offset(inst, 0, &mut out);
+    }
+    out
+}
+
+#[cfg(feature = "std")]
+struct InstList<'a, T>(pub &'a [Inst<T>]);
+
+#[cfg(feature = "std")]
+impl<T: Cell> fmt::Display for InstList<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&disasm_inst(self.0))
+    }
+}
+
+#[cfg(feature = "std")]
+struct InstWithOffsetList<'a, T>(pub &'a [InstWithOffset<T>]);
+
+#[cfg(feature = "std")]
+impl<T: Cell> fmt::Display for InstWithOffsetList<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&disasm_inst_with_offset(self.0))
+    }
+}
+
+#[cfg(feature = "std")]
+pub fn dump_stage<T: Cell>(stage: Stage, code: &str) -> Result<(), crate::error::NozomiError> {
+    use crate::vm;
+
+    let raw_insts = vm::parse::<T>(code);
+    let insts = vm::extract_loops(&raw_insts)?;
+    if stage == Stage::Parse {
+        print!("{}", InstList(&insts));
+        return Ok(());
+    }
+
+    let insts = vm::optimize_basic(&insts);
+    if stage == Stage::Basic {
+        print!("{}", InstList(&insts));
+        return Ok(());
+    }
+
+    let insts = vm::annotate_offset(&insts);
+    if stage == Stage::Offset {
+        print!("{}", InstWithOffsetList(&insts));
+        return Ok(());
+    }
+
+    let insts = vm::delay_move_ptr(&insts);
+    let insts = vm::remove_zero_move_ptr(&insts);
+    if stage == Stage::Delay {
+        print!("{}", InstWithOffsetList(&insts));
+        return Ok(());
+    }
+
+    let insts = vm::loop_to_addmul(&insts);
+    print!("{}", InstWithOffsetList(&insts));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_disasm_inst_with_offset() {
+        let insts = alloc::vec![
+            InstWithOffset::Init(0, 0u8),
+            InstWithOffset::AddMul(0, 1, 2u8),
+        ];
+        assert_eq!(
+            disasm_inst_with_offset(&insts),
+            "Init @+0 = 0\nAddMul src=+0 dst=+1 *2\n"
+        );
+    }
+}
@@ -0,0 +1,46 @@
+//! Structured errors with source positions, used instead of panicking on
+//! malformed programs or out-of-bounds pointer moves.
+
+use core::fmt;
+
+/// A position in the source text, for error messages.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Position {
+    pub byte: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NozomiError {
+    /// A `[` with no matching `]`.
+    UnmatchedOpen { pos: Position },
+    /// A `]` with no matching `[`.
+    UnmatchedClose { pos: Position },
+    /// A memory access moved the pointer out of bounds while executing the
+    /// instruction at `ip`.
+    PointerOutOfBounds { ip: usize },
+}
+
+impl fmt::Display for NozomiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NozomiError::UnmatchedOpen { pos } => write!(
+                f,
+                "unmatched '[' at line {}, column {} (byte {})",
+                pos.line, pos.column, pos.byte
+            ),
+            NozomiError::UnmatchedClose { pos } => write!(
+                f,
+                "unmatched ']' at line {}, column {} (byte {})",
+                pos.line, pos.column, pos.byte
+            ),
+            NozomiError::PointerOutOfBounds { ip } => {
+                write!(f, "pointer out of bounds while executing instruction {}", ip)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for NozomiError {}
@@ -0,0 +1,47 @@
+//! I/O abstraction so the VM core does not depend on `std`.
+
+/// What a `GetChar` should do to the current cell when the input is exhausted.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum EofBehavior {
+    /// Leave the cell unchanged.
+    #[default]
+    KeepCell,
+    /// Write 0 into the cell.
+    WriteZero,
+    /// Write 255 into the cell.
+    WriteMax,
+}
+
+/// Byte-oriented input/output, implemented by the host so the VM core can
+/// stay `no_std` + `alloc` only.
+pub trait Io {
+    /// Reads one byte, or `None` on EOF.
+    fn read_byte(&mut self) -> Option<u8>;
+    /// Writes one byte.
+    fn write_byte(&mut self, byte: u8);
+}
+
+#[cfg(feature = "std")]
+pub use self::std_io::StdIo;
+
+#[cfg(feature = "std")]
+mod std_io {
+    use super::Io;
+    use std::io::Read;
+
+    /// An [`Io`] implementation backed by `std::io::stdin`/`stdout`.
+    #[derive(Default)]
+    pub struct StdIo;
+
+    impl Io for StdIo {
+        fn read_byte(&mut self) -> Option<u8> {
+            let mut buf = [0u8];
+            std::io::stdin().lock().read_exact(&mut buf).ok()?;
+            Some(buf[0])
+        }
+
+        fn write_byte(&mut self, byte: u8) {
+            print!("{}", byte as char);
+        }
+    }
+}
@@ -0,0 +1,14 @@
+//! The `nozomi` Brainfuck VM core: parser, optimizer passes, bytecode
+//! compiler and executors. Only needs `alloc`, so it can be embedded in
+//! environments without `std` by disabling the default `std` feature.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+pub mod bytecode;
+pub mod disasm;
+pub mod error;
+pub mod io;
+pub mod memory;
+pub mod vm;
@@ -0,0 +1,181 @@
+//! A bidirectional tape of cells, generic over cell width, with a
+//! configurable out-of-bounds policy.
+
+use alloc::vec::Vec;
+use core::fmt;
+
+/// A memory cell type usable by the VM (`u8`, `u16`, `u32`).
+pub trait Cell: Copy + Default + PartialEq + fmt::Debug + fmt::Display {
+    /// The wrapping representation of `1`.
+    const ONE: Self;
+    /// The wrapping representation of `-1`, i.e. `Self::MAX`.
+    const NEG_ONE: Self;
+    fn wrapping_add(self, rhs: Self) -> Self;
+    fn wrapping_mul(self, rhs: Self) -> Self;
+    /// Widens a byte read through `Io` into a cell.
+    fn from_byte(byte: u8) -> Self;
+    /// Narrows a cell down to the byte written through `Io`.
+    fn to_byte(self) -> u8;
+    /// Sign-extends the wrapping representation, e.g. `NEG_ONE` becomes
+    /// `-1` instead of `Self::MAX`. Used to print `AddI` deltas in disasm.
+    fn as_signed(self) -> i64;
+}
+
+macro_rules! impl_cell {
+    ($ty:ty, $signed:ty) => {
+        impl Cell for $ty {
+            const ONE: Self = 1;
+            const NEG_ONE: Self = <$ty>::MAX;
+
+            fn wrapping_add(self, rhs: Self) -> Self {
+                <$ty>::wrapping_add(self, rhs)
+            }
+
+            fn wrapping_mul(self, rhs: Self) -> Self {
+                <$ty>::wrapping_mul(self, rhs)
+            }
+
+            fn from_byte(byte: u8) -> Self {
+                byte as $ty
+            }
+
+            fn to_byte(self) -> u8 {
+                self as u8
+            }
+
+            fn as_signed(self) -> i64 {
+                self as $signed as i64
+            }
+        }
+    };
+}
+
+impl_cell!(u8, i8);
+impl_cell!(u16, i16);
+impl_cell!(u32, i32);
+
+/// What to do when the pointer moves past the edge of the tape.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutOfBoundsPolicy {
+    /// Grow the tape to cover the new pointer.
+    Grow,
+    /// Wrap around a fixed-size ring the size of the current tape.
+    Wrap,
+    /// Report an error instead of accessing memory.
+    Error,
+}
+
+/// Selects the initial tape size and out-of-bounds policy. The cell width
+/// is selected by which `Memory<T>` is instantiated.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TapeConfig {
+    pub initial_size: usize,
+    pub policy: OutOfBoundsPolicy,
+}
+
+impl Default for TapeConfig {
+    fn default() -> Self {
+        TapeConfig {
+            initial_size: 30000,
+            policy: OutOfBoundsPolicy::Grow,
+        }
+    }
+}
+
+/// The pointer moved past the edge of a tape configured with
+/// [`OutOfBoundsPolicy::Error`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct OutOfBounds;
+
+/// A bidirectional tape, addressed by a signed logical pointer so moving
+/// left of the origin is legal.
+pub struct Memory<T> {
+    cells: Vec<T>,
+    origin: usize,
+    policy: OutOfBoundsPolicy,
+}
+
+impl<T: Cell> Memory<T> {
+    pub fn new(config: TapeConfig) -> Self {
+        let size = config.initial_size.max(1);
+        Memory {
+            cells: alloc::vec![T::default(); size],
+            origin: size / 2,
+            policy: config.policy,
+        }
+    }
+
+    fn physical(&mut self, ptr: isize) -> Result<usize, OutOfBounds> {
+        let target = self.origin as isize + ptr;
+        if target >= 0 && (target as usize) < self.cells.len() {
+            return Ok(target as usize);
+        }
+        match self.policy {
+            OutOfBoundsPolicy::Error => Err(OutOfBounds),
+            OutOfBoundsPolicy::Wrap => {
+                let len = self.cells.len() as isize;
+                Ok(target.rem_euclid(len) as usize)
+            }
+            OutOfBoundsPolicy::Grow => {
+                if target < 0 {
+                    let needed = (-target) as usize;
+                    let extra = needed.max(self.cells.len());
+                    let mut grown = alloc::vec![T::default(); extra];
+                    grown.extend_from_slice(&self.cells);
+                    self.cells = grown;
+                    self.origin += extra;
+                } else {
+                    let needed = target as usize + 1;
+                    let next_len = needed.max(self.cells.len() * 2);
+                    self.cells.resize(next_len, T::default());
+                }
+                Ok((self.origin as isize + ptr) as usize)
+            }
+        }
+    }
+
+    pub fn read(&mut self, ptr: isize) -> Result<T, OutOfBounds> {
+        let index = self.physical(ptr)?;
+        Ok(self.cells[index])
+    }
+
+    pub fn write(&mut self, ptr: isize, value: T) -> Result<(), OutOfBounds> {
+        let index = self.physical(ptr)?;
+        self.cells[index] = value;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_negative_pointer_grows() {
+        let mut mem: Memory<u8> = Memory::new(TapeConfig {
+            initial_size: 4,
+            policy: OutOfBoundsPolicy::Grow,
+        });
+        mem.write(-3, 7).unwrap();
+        assert_eq!(mem.read(-3).unwrap(), 7);
+    }
+
+    #[test]
+    fn test_error_policy_reports_out_of_bounds() {
+        let mut mem: Memory<u8> = Memory::new(TapeConfig {
+            initial_size: 4,
+            policy: OutOfBoundsPolicy::Error,
+        });
+        assert_eq!(mem.read(1_000_000), Err(OutOfBounds));
+    }
+
+    #[test]
+    fn test_wrap_policy_wraps_around() {
+        let mut mem: Memory<u8> = Memory::new(TapeConfig {
+            initial_size: 4,
+            policy: OutOfBoundsPolicy::Wrap,
+        });
+        mem.write(0, 9).unwrap();
+        assert_eq!(mem.read(4).unwrap(), 9);
+    }
+}
@@ -0,0 +1,325 @@
+//! The parser, optimizer and executor. This module only needs `alloc`, so it
+//! can be embedded in environments without `std`.
+
+use alloc::vec::Vec;
+
+use crate::error::{NozomiError, Position};
+use crate::io::{EofBehavior, Io};
+use crate::memory::{Cell, Memory};
+
+pub enum RawInst<T> {
+    AddI(T),
+    MovePtr(isize),
+    GetChar,
+    PutChar,
+    StartLoop(Position),
+    EndLoop(Position),
+}
+
+pub fn parse<T: Cell>(code: &str) -> Vec<RawInst<T>> {
+    let mut insts = Vec::new();
+    let mut line = 1;
+    let mut column = 1;
+    for (byte, ch) in code.char_indices() {
+        let pos = Position { byte, line, column };
+        match ch {
+            '+' => insts.push(RawInst::AddI(T::ONE)),
+            '-' => insts.push(RawInst::AddI(T::NEG_ONE)),
+            '>' => insts.push(RawInst::MovePtr(1)),
+            '<' => insts.push(RawInst::MovePtr(-1)),
+            '[' => insts.push(RawInst::StartLoop(pos)),
+            ']' => insts.push(RawInst::EndLoop(pos)),
+            '.' => insts.push(RawInst::PutChar),
+            ',' => insts.push(RawInst::GetChar),
+            _ => (),
+        }
+        if ch == '\n' {
+            line += 1;
+            column = 1;
+        } else {
+            column += 1;
+        }
+    }
+    insts
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Inst<T> {
+    AddI(T),
+    MovePtr(isize),
+    Init(T),
+    GetChar,
+    PutChar,
+    Loop(Vec<Inst<T>>),
+}
+
+pub fn extract_loops<T: Cell>(raw_insts: &[RawInst<T>]) -> Result<Vec<Inst<T>>, NozomiError> {
+    let mut loop_stack: Vec<(Vec<Inst<T>>, Option<Position>)> = alloc::vec![(Vec::new(), None)];
+    for raw_inst in raw_insts {
+        match raw_inst {
+            RawInst::AddI(x) => loop_stack.last_mut().unwrap().0.push(Inst::AddI(*x)),
+            RawInst::MovePtr(x) => loop_stack.last_mut().unwrap().0.push(Inst::MovePtr(*x)),
+            RawInst::GetChar => loop_stack.last_mut().unwrap().0.push(Inst::GetChar),
+            RawInst::PutChar => loop_stack.last_mut().unwrap().0.push(Inst::PutChar),
+            RawInst::StartLoop(pos) => loop_stack.push((Vec::new(), Some(*pos))),
+            RawInst::EndLoop(pos) => {
+                if loop_stack.len() == 1 {
+                    return Err(NozomiError::UnmatchedClose { pos: *pos });
+                }
+                let (l, _) = loop_stack.pop().unwrap();
+                loop_stack.last_mut().unwrap().0.push(Inst::Loop(l));
+            }
+        }
+    }
+    if loop_stack.len() != 1 {
+        let (_, pos) = loop_stack.pop().unwrap();
+        return Err(NozomiError::UnmatchedOpen { pos: pos.unwrap() });
+    }
+    Ok(loop_stack.pop().unwrap().0)
+}
+
+pub fn optimize_basic<T: Cell>(insts: &[Inst<T>]) -> Vec<Inst<T>> {
+    let mut result: Vec<Inst<T>> = Vec::new();
+    for inst in insts {
+        match inst {
+            Inst::AddI(x) => {
+                if let Some(Inst::AddI(y)) = result.last_mut() {
+                    *y = y.wrapping_add(*x);
+                } else if let Some(Inst::Init(y)) = result.last_mut() {
+                    *y = y.wrapping_add(*x);
+                } else {
+                    result.push(Inst::AddI(*x));
+                }
+            }
+            Inst::MovePtr(x) => {
+                if let Some(Inst::MovePtr(y)) = result.last_mut() {
+                    *y += *x;
+                } else {
+                    result.push(Inst::MovePtr(*x));
+                }
+            }
+            Inst::Loop(l) => {
+                let optimized_body = optimize_basic(l);
+                if optimized_body == alloc::vec![Inst::AddI(T::NEG_ONE)] {
+                    result.push(Inst::Init(T::default()));
+                } else {
+                    result.push(Inst::Loop(optimized_body));
+                }
+            }
+            _ => {
+                result.push(inst.clone());
+            }
+        }
+    }
+    result
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum InstWithOffset<T> {
+    AddI(isize, T),
+    MovePtr(isize),
+    Init(isize, T),
+    AddMul(isize, isize, T),
+    GetChar(isize),
+    PutChar(isize),
+    Loop(Vec<InstWithOffset<T>>),
+}
+
+pub fn annotate_offset<T: Cell>(insts: &[Inst<T>]) -> Vec<InstWithOffset<T>> {
+    insts
+        .iter()
+        .map(|inst| match inst {
+            Inst::AddI(x) => InstWithOffset::AddI(0, *x),
+            Inst::MovePtr(x) => InstWithOffset::MovePtr(*x),
+            Inst::Init(x) => InstWithOffset::Init(0, *x),
+            Inst::GetChar => InstWithOffset::GetChar(0),
+            Inst::PutChar => InstWithOffset::PutChar(0),
+            Inst::Loop(l) => InstWithOffset::Loop(annotate_offset(l)),
+        })
+        .collect()
+}
+
+pub fn delay_move_ptr<T: Cell>(insts: &[InstWithOffset<T>]) -> Vec<InstWithOffset<T>> {
+    let mut offset = 0;
+    let mut result = Vec::new();
+    for inst in insts {
+        match inst {
+            InstWithOffset::AddI(ofs, x) => result.push(InstWithOffset::AddI(ofs + offset, *x)),
+            InstWithOffset::MovePtr(x) => offset += *x,
+            InstWithOffset::Init(ofs, x) => result.push(InstWithOffset::Init(ofs + offset, *x)),
+            InstWithOffset::AddMul(ofs1, ofs2, x) => {
+                result.push(InstWithOffset::AddMul(*ofs1 + offset, *ofs2 + offset, *x))
+            }
+            InstWithOffset::GetChar(ofs) => result.push(InstWithOffset::GetChar(ofs + offset)),
+            InstWithOffset::PutChar(ofs) => result.push(InstWithOffset::PutChar(ofs + offset)),
+            InstWithOffset::Loop(l) => {
+                result.push(InstWithOffset::MovePtr(offset));
+                offset = 0;
+                let new_l = delay_move_ptr(l);
+                result.push(InstWithOffset::Loop(new_l));
+            }
+        }
+    }
+    if offset != 0 {
+        result.push(InstWithOffset::MovePtr(offset));
+    }
+    result
+}
+
+pub fn remove_zero_move_ptr<T: Cell>(insts: &[InstWithOffset<T>]) -> Vec<InstWithOffset<T>> {
+    insts
+        .iter()
+        .filter_map(|inst| match inst {
+            InstWithOffset::MovePtr(x) => {
+                if *x != 0 {
+                    Some(inst.clone())
+                } else {
+                    None
+                }
+            }
+            InstWithOffset::Loop(l) => Some(InstWithOffset::Loop(remove_zero_move_ptr(l))),
+            _ => Some(inst.clone()),
+        })
+        .collect()
+}
+
+fn loop_to_addmul_body<T: Cell>(insts: &[InstWithOffset<T>]) -> Option<Vec<InstWithOffset<T>>> {
+    let mut base_add = T::default();
+    let mut add_ops = Vec::new();
+    for inst in insts {
+        if let InstWithOffset::AddI(ofs, x) = inst {
+            if *ofs == 0 {
+                base_add = base_add.wrapping_add(*x);
+            } else {
+                add_ops.push((*ofs, *x));
+            }
+        } else {
+            return None;
+        }
+    }
+    if base_add != T::NEG_ONE {
+        return None;
+    }
+    let mut result = Vec::new();
+    for (ofs, x) in add_ops {
+        result.push(InstWithOffset::AddMul(0, ofs, x));
+    }
+    result.push(InstWithOffset::Init(0, T::default()));
+    Some(result)
+}
+
+pub fn loop_to_addmul<T: Cell>(insts: &[InstWithOffset<T>]) -> Vec<InstWithOffset<T>> {
+    insts
+        .iter()
+        .map(|inst| match inst {
+            InstWithOffset::Loop(l) => {
+                if let Some(new_insts) = loop_to_addmul_body(l) {
+                    InstWithOffset::Loop(new_insts)
+                } else {
+                    InstWithOffset::Loop(loop_to_addmul(l))
+                }
+            }
+            _ => inst.clone(),
+        })
+        .collect()
+}
+
+pub fn exec_body<T: Cell>(
+    insts: &[InstWithOffset<T>],
+    memory: &mut Memory<T>,
+    ptr: &mut isize,
+    cycle_count: &mut usize,
+    io: &mut impl Io,
+    eof: EofBehavior,
+) -> Result<(), NozomiError> {
+    for inst in insts {
+        let ip = *cycle_count;
+        *cycle_count += 1;
+        let oob = || NozomiError::PointerOutOfBounds { ip };
+        match inst {
+            InstWithOffset::AddI(ofs, x) => {
+                let index = ptr.checked_add(*ofs).ok_or_else(oob)?;
+                let value = memory.read(index).map_err(|_| oob())?;
+                memory.write(index, value.wrapping_add(*x)).map_err(|_| oob())?;
+            }
+            InstWithOffset::MovePtr(x) => {
+                *ptr = ptr.checked_add(*x).ok_or_else(oob)?;
+            }
+            InstWithOffset::Init(ofs, x) => {
+                let index = ptr.checked_add(*ofs).ok_or_else(oob)?;
+                memory.write(index, *x).map_err(|_| oob())?;
+            }
+            InstWithOffset::AddMul(ofs1, ofs2, x) => {
+                let index1 = ptr.checked_add(*ofs1).ok_or_else(oob)?;
+                let index2 = ptr.checked_add(*ofs2).ok_or_else(oob)?;
+                let src = memory.read(index1).map_err(|_| oob())?;
+                let dst = memory.read(index2).map_err(|_| oob())?;
+                memory
+                    .write(index2, dst.wrapping_add(src.wrapping_mul(*x)))
+                    .map_err(|_| oob())?;
+            }
+            InstWithOffset::GetChar(ofs) => {
+                let index = ptr.checked_add(*ofs).ok_or_else(oob)?;
+                match io.read_byte() {
+                    Some(b) => memory.write(index, T::from_byte(b)),
+                    None => match eof {
+                        EofBehavior::KeepCell => Ok(()),
+                        EofBehavior::WriteZero => memory.write(index, T::default()),
+                        EofBehavior::WriteMax => memory.write(index, T::NEG_ONE),
+                    },
+                }
+                .map_err(|_| oob())?;
+            }
+            InstWithOffset::PutChar(ofs) => {
+                let index = ptr.checked_add(*ofs).ok_or_else(oob)?;
+                let value = memory.read(index).map_err(|_| oob())?;
+                io.write_byte(value.to_byte());
+            }
+            InstWithOffset::Loop(l) => {
+                while memory.read(*ptr).map_err(|_| oob())? != T::default() {
+                    exec_body(l, memory, ptr, cycle_count, io, eof)?;
+                    *cycle_count += 1;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+pub fn exec<T: Cell>(
+    insts: &[InstWithOffset<T>],
+    memory: &mut Memory<T>,
+    io: &mut impl Io,
+    eof: EofBehavior,
+) -> Result<usize, NozomiError> {
+    let mut ptr = 0isize;
+    let mut cycle_count = 0;
+    exec_body(insts, memory, &mut ptr, &mut cycle_count, io, eof)?;
+    Ok(cycle_count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_loop_to_addmul() {
+        let insts = [
+            InstWithOffset::AddI(0, 2u8),
+            InstWithOffset::MovePtr(2),
+            InstWithOffset::Loop(alloc::vec![
+                InstWithOffset::AddI(0, 255u8),
+                InstWithOffset::AddI(1, 1u8),
+            ]),
+        ];
+        assert_eq!(
+            loop_to_addmul(&insts),
+            alloc::vec![
+                InstWithOffset::AddI(0, 2u8),
+                InstWithOffset::MovePtr(2),
+                InstWithOffset::AddMul(0, 1, 1u8),
+                InstWithOffset::Init(0, 0u8)
+            ]
+        );
+    }
+}